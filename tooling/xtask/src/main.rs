@@ -1,7 +1,11 @@
-use std::process::Command;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
 
 use anyhow::{bail, Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
 
 #[derive(Parser)]
 #[command(name = "cargo xtask")]
@@ -14,6 +18,13 @@ struct Args {
 enum CliCommand {
     /// Runs `cargo clippy`.
     Clippy(ClippyArgs),
+    /// Finds allowed/warned rules in the lint policy that have no remaining
+    /// violations and can be promoted to fully enforced.
+    Prune(PruneArgs),
+    /// Writes the lint policy into the workspace Cargo.toml's
+    /// `[workspace.lints.clippy]` table, so plain `cargo clippy` and
+    /// editor-integrated clippy honor the same policy as this xtask.
+    SyncLints(SyncLintsArgs),
 }
 
 fn main() -> Result<()> {
@@ -21,56 +32,133 @@ fn main() -> Result<()> {
 
     match args.command {
         CliCommand::Clippy(args) => run_clippy(args),
+        CliCommand::Prune(args) => run_prune(args),
+        CliCommand::SyncLints(args) => run_sync_lints(args),
     }
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum MessageFormat {
+    /// Emit a machine-readable JSON lint report instead of clippy's normal
+    /// human output.
+    Json,
+}
+
 #[derive(Parser)]
 struct ClippyArgs {
     /// Automatically apply lint suggestions (`clippy --fix`).
-    #[arg(long)]
+    #[arg(long, conflicts_with = "message_format")]
     fix: bool,
 
     /// The package to run Clippy against (`cargo -p <PACKAGE> clippy`).
     #[arg(long, short)]
     package: Option<String>,
+
+    /// Emit a structured lint report instead of clippy's normal output.
+    #[arg(long)]
+    message_format: Option<MessageFormat>,
+
+    /// Forwarded to `cargo fix`/clippy: apply fixes even if the tree has
+    /// pre-existing compiler errors.
+    #[arg(long)]
+    broken_code: bool,
+
+    /// Forwarded to `cargo fix`/clippy: fix even if the working directory
+    /// has uncommitted changes.
+    #[arg(long)]
+    allow_dirty: bool,
+
+    /// Forwarded to `cargo fix`/clippy: fix even if the working directory
+    /// has staged changes.
+    #[arg(long)]
+    allow_staged: bool,
 }
 
-fn run_clippy(args: ClippyArgs) -> Result<()> {
-    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+#[derive(Parser)]
+struct PruneArgs {
+    /// The package to run Clippy against (`cargo -p <PACKAGE> clippy`).
+    #[arg(long, short)]
+    package: Option<String>,
+}
 
-    let mut clippy_command = Command::new(&cargo);
-    clippy_command.arg("clippy");
+#[derive(Parser)]
+struct SyncLintsArgs {
+    /// Fail instead of writing, if the checked-in table has drifted from
+    /// the lint policy. Intended for CI.
+    #[arg(long)]
+    check: bool,
+}
 
-    if let Some(package) = args.package {
-        clippy_command.args(["--package", &package]);
-    } else {
-        clippy_command.arg("--workspace");
-    }
+/// The clippy lint groups, as opposed to individual lints. Groups need the
+/// `{ level = "allow", priority = -1 }` form in `[workspace.lints.clippy]`
+/// so that more specific lints enabled elsewhere can still override them.
+const CLIPPY_GROUPS: &[&str] = &[
+    "clippy::all",
+    "clippy::correctness",
+    "clippy::suspicious",
+    "clippy::style",
+    "clippy::complexity",
+    "clippy::perf",
+    "clippy::pedantic",
+    "clippy::nursery",
+    "clippy::cargo",
+    "clippy::restriction",
+];
 
-    clippy_command
-        .arg("--release")
-        .arg("--all-targets")
-        .arg("--all-features");
+/// The level to apply a lint or lint group at, mirroring clippy's own
+/// `-A`/`-W`/`-D`/`-F` flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+    Forbid,
+}
 
-    if args.fix {
-        clippy_command.arg("--fix");
+impl LintLevel {
+    fn cargo_flag(self) -> &'static str {
+        match self {
+            LintLevel::Allow => "--allow",
+            LintLevel::Warn => "--warn",
+            LintLevel::Deny => "--deny",
+            LintLevel::Forbid => "--forbid",
+        }
     }
 
-    clippy_command.arg("--");
+    fn toml_str(self) -> &'static str {
+        match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warn",
+            LintLevel::Deny => "deny",
+            LintLevel::Forbid => "forbid",
+        }
+    }
+}
 
-    // Deny all warnings.
-    // We don't do this yet on Windows, as it still has some warnings present.
-    #[cfg(not(target_os = "windows"))]
-    clippy_command.args(["--deny", "warnings"]);
+/// Name of the optional, checked-in config file that overrides
+/// [`default_lint_policy`].
+const LINT_POLICY_FILE_NAME: &str = "xtask-lints.toml";
 
-    /// These are all of the rules that currently have violations in the Zed
-    /// codebase.
-    ///
-    /// We'll want to drive this list down by either:
-    /// 1. fixing violations of the rule and begin enforcing it
-    /// 2. deciding we want to allow the rule permanently, at which point
-    ///    we should codify that separately in this script.
-    const MIGRATORY_RULES_TO_ALLOW: &[&str] = &[
+#[derive(Deserialize)]
+struct LintPolicyFile {
+    #[serde(flatten)]
+    levels: BTreeMap<String, LintLevel>,
+}
+
+/// These are all of the rules that currently have violations in the Zed
+/// codebase, and the rules we deny outright.
+///
+/// We'll want to drive the allowed rules down by either:
+/// 1. fixing violations of the rule and begin enforcing it
+/// 2. deciding we want to allow the rule permanently, at which point
+///    we should codify that separately in `xtask-lints.toml`.
+///
+/// This is the fallback used when no `xtask-lints.toml` is present, so
+/// existing behavior is unchanged for repos that haven't opted into the
+/// config file yet.
+fn default_lint_policy() -> BTreeMap<String, LintLevel> {
+    let mut levels: BTreeMap<String, LintLevel> = [
         // There's a bunch of rules currently failing in the `style` group, so
         // allow all of those, for now.
         "clippy::style",
@@ -138,21 +226,145 @@ fn run_clippy(args: ClippyArgs) -> Result<()> {
         "clippy::useless_conversion",
         "clippy::useless_format",
         "clippy::vec_init_then_push",
-    ];
-
-    // When fixing violations automatically we don't care about the
-    // rules we're already violating, since it may be possible to
-    // have them fixed automatically.
-    if !args.fix {
-        for rule in MIGRATORY_RULES_TO_ALLOW {
-            clippy_command.args(["--allow", rule]);
+    ]
+    .into_iter()
+    .map(|rule| (rule.to_string(), LintLevel::Allow))
+    .collect();
+
+    // Deny `dbg!` and `todo!`s.
+    levels.insert("clippy::dbg_macro".to_string(), LintLevel::Deny);
+    levels.insert("clippy::todo".to_string(), LintLevel::Deny);
+
+    levels
+}
+
+/// Loads the lint policy from `xtask-lints.toml` at the workspace root, or
+/// falls back to [`default_lint_policy`] if no such file exists.
+fn load_lint_policy() -> Result<BTreeMap<String, LintLevel>> {
+    let path = lint_policy_path();
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            let file: LintPolicyFile = toml::from_str(&contents)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            for rule in file.levels.keys() {
+                if !rule.starts_with("clippy::") {
+                    bail!(
+                        "{} configures {rule:?}, which isn't `clippy::`-namespaced; \
+                         xtask-lints.toml only configures clippy lints and groups, so keys \
+                         must be written in full, e.g. \"clippy::{rule}\"",
+                        path.display()
+                    );
+                }
+            }
+            Ok(file.levels)
         }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(default_lint_policy()),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+fn lint_policy_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../..")
+        .join(LINT_POLICY_FILE_NAME)
+}
+
+/// The rules the policy doesn't fully enforce yet (`allow` or `warn`),
+/// i.e. candidates for driving down and eventually promoting to `deny`.
+///
+/// Lint groups (see [`CLIPPY_GROUPS`]) are left out: diagnostics are
+/// reported under the individual lint's name, never the group's, so a
+/// group's violations can't be counted from them.
+fn migratory_rules(policy: &BTreeMap<String, LintLevel>) -> impl Iterator<Item = &str> {
+    policy.iter().filter_map(|(rule, level)| {
+        (matches!(level, LintLevel::Allow | LintLevel::Warn)
+            && !CLIPPY_GROUPS.contains(&rule.as_str()))
+        .then_some(rule.as_str())
+    })
+}
+
+/// Translates a lint policy into `--allow`/`--warn`/`--deny`/`--forbid`
+/// flags for clippy, in the order clippy expects: lint groups first (so
+/// they act as a baseline), then specific rules (so they can override a
+/// group they belong to).
+fn lint_policy_flags(policy: &BTreeMap<String, LintLevel>) -> Vec<String> {
+    let (groups, specific): (Vec<_>, Vec<_>) = policy
+        .iter()
+        .partition(|(rule, _)| CLIPPY_GROUPS.contains(&rule.as_str()));
+
+    groups
+        .into_iter()
+        .chain(specific)
+        .flat_map(|(rule, level)| [level.cargo_flag().to_string(), rule.clone()])
+        .collect()
+}
+
+fn run_clippy(args: ClippyArgs) -> Result<()> {
+    if let Some(MessageFormat::Json) = args.message_format {
+        return run_clippy_json_report(args.package.as_deref());
+    }
+
+    let fix = args.fix;
+    let package = args.package.clone();
+
+    run_clippy_command(&args)?;
+
+    if fix {
+        report_broken_fixes(package.as_deref())?;
+    }
+
+    Ok(())
+}
+
+fn run_clippy_command(args: &ClippyArgs) -> Result<()> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+
+    let mut clippy_command = Command::new(&cargo);
+    clippy_command.arg("clippy");
+
+    if let Some(package) = &args.package {
+        clippy_command.args(["--package", package]);
+    } else {
+        clippy_command.arg("--workspace");
     }
 
-    // Deny `dbg!` and `todo!`s.
     clippy_command
-        .args(["--deny", "clippy::dbg_macro"])
-        .args(["--deny", "clippy::todo"]);
+        .arg("--release")
+        .arg("--all-targets")
+        .arg("--all-features");
+
+    if args.fix {
+        clippy_command.arg("--fix");
+
+        if args.broken_code {
+            clippy_command.arg("--broken-code");
+        }
+        if args.allow_dirty {
+            clippy_command.arg("--allow-dirty");
+        }
+        if args.allow_staged {
+            clippy_command.arg("--allow-staged");
+        }
+    }
+
+    clippy_command.arg("--");
+
+    // Deny all warnings.
+    // We don't do this yet on Windows, as it still has some warnings present.
+    #[cfg(not(target_os = "windows"))]
+    clippy_command.args(["--deny", "warnings"]);
+
+    let mut policy = load_lint_policy()?;
+
+    // When fixing violations automatically we don't care about the rules
+    // we're already violating (it may be possible to have them fixed
+    // automatically), but we still want to enforce `deny`/`forbid` rules
+    // like `dbg_macro`/`todo`.
+    if args.fix {
+        policy.retain(|_, level| matches!(level, LintLevel::Deny | LintLevel::Forbid));
+    }
+
+    clippy_command.args(lint_policy_flags(&policy));
 
     eprintln!(
         "running: {cargo} {}",
@@ -175,3 +387,373 @@ fn run_clippy(args: ClippyArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// One `cargo`-emitted JSON line we care about. Cargo also emits
+/// `build-script-executed`, `build-finished`, etc., which we ignore.
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    #[serde(default)]
+    message: Option<ClippyDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct ClippyDiagnostic {
+    code: Option<ClippyCode>,
+    spans: Vec<ClippySpan>,
+    #[serde(default)]
+    children: Vec<ClippyChildDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct ClippyChildDiagnostic {
+    #[serde(default)]
+    spans: Vec<ClippySpan>,
+}
+
+#[derive(Deserialize)]
+struct ClippyCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct ClippySpan {
+    file_name: String,
+    is_primary: bool,
+    #[serde(default)]
+    suggestion_applicability: Option<String>,
+}
+
+/// Whether clippy considered any of this diagnostic's suggestions safe to
+/// apply automatically. If such a diagnostic is still present after
+/// `--fix` has run, its suggestion was never actually applied.
+fn has_machine_applicable_suggestion(diagnostic: &ClippyDiagnostic) -> bool {
+    diagnostic
+        .children
+        .iter()
+        .flat_map(|child| &child.spans)
+        .any(|span| span.suggestion_applicability.as_deref() == Some("MachineApplicable"))
+}
+
+/// Re-runs clippy in JSON mode after `--fix` and reports any lint that had
+/// a machine-applicable suggestion but is still present in the tree — the
+/// classic signal of a broken or conflicting autofix.
+fn report_broken_fixes(package: Option<&str>) -> Result<()> {
+    let diagnostics = collect_clippy_diagnostics(package)?;
+    let broken: Vec<&ClippyDiagnostic> = diagnostics
+        .iter()
+        .filter(|diagnostic| has_machine_applicable_suggestion(diagnostic))
+        .collect();
+
+    if broken.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("the following machine-applicable suggestions were not applied by --fix:");
+    for diagnostic in broken {
+        let Some(code) = &diagnostic.code else {
+            continue;
+        };
+        let file = diagnostic
+            .spans
+            .iter()
+            .find(|span| span.is_primary)
+            .map(|span| span.file_name.as_str())
+            .unwrap_or("<unknown>");
+        eprintln!("  {} in {file}", code.code);
+    }
+
+    bail!("--fix left machine-applicable suggestions unapplied; see above");
+}
+
+/// A single occurrence of a lint violation, as reported by `--message-format
+/// json`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+struct Location {
+    file: String,
+}
+
+#[derive(serde::Serialize)]
+struct ClippyJsonReport {
+    /// Every diagnosed lint, grouped by its fully-qualified rule name
+    /// (e.g. `clippy::needless_lifetimes`).
+    by_lint: BTreeMap<String, Vec<Location>>,
+    /// Violation counts restricted to the policy's migratory (`allow`/`warn`)
+    /// rules, so maintainers can see at a glance how many are left for each
+    /// rule we're not fully enforcing yet.
+    migratory_rule_counts: BTreeMap<String, usize>,
+}
+
+/// Runs clippy across the workspace (or a single package) with
+/// `--message-format=json` and none of the `--allow`s we normally pass,
+/// so that every rule's violations show up in the output, then
+/// aggregates them into a [`ClippyJsonReport`].
+fn run_clippy_json_report(package: Option<&str>) -> Result<()> {
+    let diagnostics = collect_clippy_diagnostics(package)?;
+    let by_lint = group_diagnostics_by_lint(&diagnostics);
+
+    let policy = load_lint_policy()?;
+    let migratory_rule_counts = migratory_rules(&policy)
+        .map(|rule| {
+            let count = by_lint.get(rule).map_or(0, Vec::len);
+            (rule.to_string(), count)
+        })
+        .collect();
+
+    let report = ClippyJsonReport {
+        by_lint,
+        migratory_rule_counts,
+    };
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    eprintln!("{:<50} {:>6}", "rule", "count");
+    for (rule, count) in &report.migratory_rule_counts {
+        eprintln!("{rule:<50} {count:>6}");
+    }
+
+    Ok(())
+}
+
+/// Groups parsed diagnostics by their fully-qualified rule name, recording
+/// where each violation occurred.
+fn group_diagnostics_by_lint(diagnostics: &[ClippyDiagnostic]) -> BTreeMap<String, Vec<Location>> {
+    let mut by_lint: BTreeMap<String, Vec<Location>> = BTreeMap::new();
+    for diagnostic in diagnostics {
+        let Some(code) = &diagnostic.code else {
+            continue;
+        };
+        let Some(primary_span) = diagnostic.spans.iter().find(|span| span.is_primary) else {
+            continue;
+        };
+        by_lint
+            .entry(code.code.clone())
+            .or_default()
+            .push(Location {
+                file: primary_span.file_name.clone(),
+            });
+    }
+    by_lint
+}
+
+/// Runs a single JSON-capturing clippy pass with none of the migratory
+/// allows applied, then reports every migratory (`allow`/`warn`) rule
+/// whose code never showed up as a candidate for permanent enforcement.
+///
+/// We deliberately avoid running clippy once per rule (with `--deny <rule>`
+/// and `--allow` for everything else): a single workspace pass with nothing
+/// allowed gives us the same answer far more cheaply.
+fn run_prune(args: PruneArgs) -> Result<()> {
+    let diagnostics = collect_clippy_diagnostics(args.package.as_deref())?;
+    let by_lint = group_diagnostics_by_lint(&diagnostics);
+
+    let policy = load_lint_policy()?;
+    let candidates: Vec<&str> = migratory_rules(&policy)
+        .filter(|rule| !by_lint.contains_key(*rule))
+        .collect();
+
+    if candidates.is_empty() {
+        println!("no migratory rules are ready to be promoted out of the allow-list");
+        return Ok(());
+    }
+
+    println!("rules with zero remaining violations (safe to promote to deny/forbid):");
+    for rule in candidates {
+        println!("  {rule}");
+    }
+
+    Ok(())
+}
+
+/// Path to the workspace root's `Cargo.toml`, relative to this crate.
+fn workspace_manifest_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../Cargo.toml")
+}
+
+/// Builds the `[workspace.lints.clippy]` table equivalent to the lint
+/// policy, using the dotted-key-free inline-table form for groups (some
+/// downstream TOML parsers mishandle dotted keys).
+fn desired_lints_table(policy: &BTreeMap<String, LintLevel>) -> toml_edit::Table {
+    let mut table = toml_edit::Table::new();
+    for (rule, level) in policy {
+        let name = rule.trim_start_matches("clippy::");
+        debug_assert!(
+            !table.contains_key(name),
+            "two policy rules ({rule:?}) collide once the `clippy::` prefix is stripped; \
+             `load_lint_policy` should have rejected the unprefixed one"
+        );
+        if CLIPPY_GROUPS.contains(&rule.as_str()) {
+            let mut entry = toml_edit::InlineTable::new();
+            entry.insert("level", level.toml_str().into());
+            entry.insert("priority", (-1).into());
+            table.insert(name, toml_edit::Item::Value(toml_edit::Value::InlineTable(entry)));
+        } else {
+            table.insert(name, toml_edit::value(level.toml_str()));
+        }
+    }
+    table
+}
+
+/// Looks up `[workspace.lints.clippy]` without panicking if any segment of
+/// the path is missing (e.g. a workspace `Cargo.toml` that has no `lints`
+/// table yet).
+fn get_clippy_lints_table(document: &toml_edit::DocumentMut) -> Option<&toml_edit::Item> {
+    document
+        .get("workspace")
+        .and_then(|workspace| workspace.get("lints"))
+        .and_then(|lints| lints.get("clippy"))
+}
+
+/// Returns a mutable reference to `[workspace.lints.clippy]`, creating
+/// `workspace.lints` and/or `workspace.lints.clippy` as empty tables if
+/// they don't already exist, rather than relying on `toml_edit`'s chained
+/// `IndexMut` (which only auto-vivifies one level and silently drops the
+/// rest).
+fn clippy_lints_table_mut(document: &mut toml_edit::DocumentMut) -> Result<&mut toml_edit::Table> {
+    let workspace = document
+        .as_table_mut()
+        .entry("workspace")
+        .or_insert(toml_edit::Item::Table(toml_edit::Table::new()))
+        .as_table_mut()
+        .context("`workspace` is not a table")?;
+    let lints = workspace
+        .entry("lints")
+        .or_insert(toml_edit::Item::Table(toml_edit::Table::new()))
+        .as_table_mut()
+        .context("`workspace.lints` is not a table")?;
+    lints
+        .entry("clippy")
+        .or_insert(toml_edit::Item::Table(toml_edit::Table::new()))
+        .as_table_mut()
+        .context("`workspace.lints.clippy` is not a table")
+}
+
+/// Syncs the lint policy into the workspace `Cargo.toml`'s
+/// `[workspace.lints.clippy]` table, keeping the policy (either
+/// `xtask-lints.toml` or [`default_lint_policy`]) as the single source of
+/// truth.
+fn run_sync_lints(args: SyncLintsArgs) -> Result<()> {
+    let manifest_path = workspace_manifest_path();
+    let contents = std::fs::read_to_string(&manifest_path)
+        .with_context(|| format!("failed to read {}", manifest_path.display()))?;
+    let mut document: toml_edit::DocumentMut = contents
+        .parse()
+        .with_context(|| format!("failed to parse {}", manifest_path.display()))?;
+
+    let policy = load_lint_policy()?;
+    let desired = desired_lints_table(&policy);
+    let desired_toml = toml_edit::Item::Table(desired.clone()).to_string();
+    let existing_toml = get_clippy_lints_table(&document)
+        .map(|item| item.to_string())
+        .unwrap_or_default();
+
+    if args.check {
+        if existing_toml.trim() != desired_toml.trim() {
+            bail!(
+                "{} is out of sync with the lint policy; run `cargo xtask sync-lints` to update it",
+                manifest_path.display()
+            );
+        }
+        println!("{} is in sync with the lint policy", manifest_path.display());
+        return Ok(());
+    }
+
+    *clippy_lints_table_mut(&mut document)? = desired;
+    std::fs::write(&manifest_path, document.to_string())
+        .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+    println!("wrote {}", manifest_path.display());
+
+    Ok(())
+}
+
+/// Runs clippy with `--message-format=json` and parses every
+/// `compiler-message` it emits into a [`ClippyDiagnostic`].
+fn collect_clippy_diagnostics(package: Option<&str>) -> Result<Vec<ClippyDiagnostic>> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+
+    let mut clippy_command = Command::new(&cargo);
+    clippy_command.arg("clippy");
+
+    if let Some(package) = package {
+        clippy_command.args(["--package", package]);
+    } else {
+        clippy_command.arg("--workspace");
+    }
+
+    clippy_command
+        .arg("--release")
+        .arg("--all-targets")
+        .arg("--all-features")
+        .arg("--message-format=json")
+        .stdout(Stdio::piped());
+
+    eprintln!(
+        "running: {cargo} {}",
+        clippy_command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+
+    let mut child = clippy_command
+        .spawn()
+        .context("failed to spawn child process")?;
+    let stdout = child.stdout.take().context("no stdout on child process")?;
+
+    let mut diagnostics = Vec::new();
+    for line in std::io::BufReader::new(stdout).lines() {
+        let line = line.context("failed to read clippy output")?;
+        let message: CargoMessage = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            // Cargo interleaves non-JSON lines (e.g. from build scripts) on
+            // stdout; skip anything we can't parse.
+            Err(_) => continue,
+        };
+        if message.reason != "compiler-message" {
+            continue;
+        }
+        if let Some(diagnostic) = message.message {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    // We intentionally don't bail on a non-zero exit status here: clippy
+    // exits non-zero whenever any diagnostic is an error (e.g. a
+    // deny-by-default lint), and those diagnostics are still wanted here.
+    child
+        .wait()
+        .context("failed to wait for child process")?;
+
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod sync_lints_tests {
+    use super::*;
+
+    #[test]
+    fn clippy_lints_table_mut_creates_missing_tables() {
+        let mut document: toml_edit::DocumentMut =
+            "[workspace]\nmembers = [\"a\"]\n".parse().unwrap();
+        assert!(get_clippy_lints_table(&document).is_none());
+
+        let mut policy = BTreeMap::new();
+        policy.insert("clippy::style".to_string(), LintLevel::Allow);
+        policy.insert("clippy::needless_lifetimes".to_string(), LintLevel::Allow);
+        let desired = desired_lints_table(&policy);
+
+        *clippy_lints_table_mut(&mut document).unwrap() = desired;
+
+        let clippy = get_clippy_lints_table(&document)
+            .and_then(|item| item.as_table())
+            .expect("clippy table should have been created");
+        assert_eq!(clippy["needless_lifetimes"].as_str(), Some("allow"));
+        assert!(clippy["style"].as_inline_table().is_some());
+        // The pre-existing `members` key must survive untouched.
+        assert_eq!(
+            document["workspace"]["members"].as_array().unwrap().len(),
+            1
+        );
+    }
+}